@@ -8,6 +8,11 @@ use os_str_bytes::RawOsStr;
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub(crate) struct RawArgs {
     items: Vec<OsString>,
+    argfiles_enabled: bool,
+    /// Set by the lazy expansion in [`RawArgs::next_os`] when [`RawArgs::expand_argfile_at`]
+    /// fails, since that path has no `Result` to hand the error back through. Stored as a
+    /// formatted message rather than `io::Error` so `RawArgs` can keep deriving `Clone`/`Eq`.
+    last_argfile_error: Option<String>,
 }
 
 impl RawArgs {
@@ -15,16 +20,58 @@ impl RawArgs {
         ArgCursor::new()
     }
 
-    pub fn next(&self, cursor: &mut ArgCursor) -> Option<ParsedArg<'_>> {
+    /// Enable GNU/`gcc`-style `@file` response-file expansion
+    ///
+    /// When enabled, [`RawArgs::next`]/[`RawArgs::next_os`] transparently expand any `@path`
+    /// token they encounter, the same way [`RawArgs::expand_argfiles`] does, before handing it back to the
+    /// caller, so callers that want `@argfile` ergonomics don't have to drive expansion
+    /// out-of-band. [`RawArgs::peek`]/[`RawArgs::peek_os`] are unaffected, since expanding would
+    /// mutate the argument list out from under a non-consuming look-ahead.
+    pub fn set_argfiles_enabled(&mut self, yes: bool) {
+        self.argfiles_enabled = yes;
+    }
+
+    pub fn next(&mut self, cursor: &mut ArgCursor) -> Option<ParsedArg<'_>> {
         self.next_os(cursor).map(ParsedArg::new)
     }
 
-    pub fn next_os(&self, cursor: &mut ArgCursor) -> Option<&OsStr> {
+    pub fn next_os(&mut self, cursor: &mut ArgCursor) -> Option<&OsStr> {
+        if self.argfiles_enabled {
+            self.last_argfile_error = None;
+            // Keep expanding at `cursor.cursor`: an `@file` that expands to zero tokens (empty,
+            // whitespace-only, ...) shifts the next item into this slot without advancing past it,
+            // and that item may itself be an `@file` token that still needs expanding. Mirrors
+            // the loop in the eager `expand_argfiles`, which re-visits `pos` the same way.
+            loop {
+                match self.expand_argfile_at(cursor.cursor) {
+                    // Best-effort: on a read/encoding error, fall through and hand back the
+                    // literal `@path` token so the caller can report it like any other argument.
+                    // The error itself isn't lost, though: stash it so callers that want a real
+                    // diagnostic (rather than just an "unknown argument") can retrieve it via
+                    // `RawArgs::take_argfile_error`.
+                    Err(err) => {
+                        self.last_argfile_error = Some(err.to_string());
+                        break;
+                    }
+                    Ok(Some(0)) => continue,
+                    Ok(_) => break,
+                }
+            }
+        }
         let next = self.items.get(cursor.cursor).map(|s| s.as_os_str());
         cursor.cursor = cursor.cursor.saturating_add(1);
         next
     }
 
+    /// Take the error (if any) from the most recent lazy `@file` expansion performed by
+    /// [`RawArgs::next`]/[`RawArgs::next_os`]
+    ///
+    /// Returns `None` if argfile expansion is disabled, the last expansion succeeded, or this
+    /// has already been called since the last expansion.
+    pub fn take_argfile_error(&mut self) -> Option<String> {
+        self.last_argfile_error.take()
+    }
+
     pub fn peek(&self, cursor: &ArgCursor) -> Option<ParsedArg<'_>> {
         self.peek_os(cursor).map(ParsedArg::new)
     }
@@ -56,6 +103,213 @@ impl RawArgs {
             insert_items.iter().map(OsString::from),
         );
     }
+
+    /// Inject arguments before the [`RawArgs::next`]
+    ///
+    /// Sibling to [`RawArgs::insert`] for values that may not be valid UTF-8, such as tokens
+    /// read from an `@file` response file.
+    pub fn insert_os(
+        &mut self,
+        cursor: &ArgCursor,
+        insert_items: impl IntoIterator<Item = OsString>,
+    ) {
+        self.items
+            .splice(cursor.cursor..cursor.cursor, insert_items);
+    }
+
+    /// Expand every `@file` response-file argument from `cursor` to the end, GNU/`gcc`-style
+    ///
+    /// This is the eager counterpart to [`RawArgs::set_argfiles_enabled`], for callers that want
+    /// the whole remaining argument list expanded up front rather than lazily as [`RawArgs::next`]
+    /// walks it.
+    pub fn expand_argfiles(&mut self, cursor: &ArgCursor) -> std::io::Result<()> {
+        let mut pos = cursor.cursor;
+        while pos < self.items.len() {
+            match self.expand_argfile_at(pos)? {
+                Some(inserted) => pos += inserted,
+                None => pos += 1,
+            }
+        }
+        Ok(())
+    }
+
+    /// If `self.items[pos]` is an `@path` token, replace it in place with the (recursively
+    /// expanded) tokens read from `path`, via [`RawArgs::insert_os`], and return how many
+    /// tokens were inserted. Otherwise a no-op.
+    fn expand_argfile_at(&mut self, pos: usize) -> std::io::Result<Option<usize>> {
+        let Some(path) = self.items.get(pos).and_then(argfile_path) else {
+            return Ok(None);
+        };
+
+        let mut budget = MAX_ARGFILE_TOKENS;
+        let tokens = read_argfile(path, 0, &mut budget)?;
+        let inserted = tokens.len();
+        self.items.remove(pos);
+        self.insert_os(&ArgCursor { cursor: pos }, tokens);
+        Ok(Some(inserted))
+    }
+}
+
+/// Max depth of nested `@file` expansion, guarding against self-referential response files.
+const MAX_ARGFILE_DEPTH: usize = 10;
+
+/// Max number of arguments a single (possibly nested) `@file` expansion may produce, guarding
+/// against a response file that fans out breadth-wise into many other files without any single
+/// expansion cycle ever repeating.
+const MAX_ARGFILE_TOKENS: usize = 100_000;
+
+fn argfile_path(item: &OsString) -> Option<&str> {
+    item.to_str()?.strip_prefix('@')
+}
+
+fn read_argfile(path: &str, depth: usize, budget: &mut usize) -> std::io::Result<Vec<OsString>> {
+    if MAX_ARGFILE_DEPTH <= depth {
+        return Err(std::io::Error::other(format!(
+            "@{path}: too many levels of @file nesting"
+        )));
+    }
+
+    let contents = std::fs::read(path)?;
+    let mut expanded = Vec::new();
+    for token in tokenize_argfile(&contents)? {
+        // Charge the budget per token *seen*, not per token emitted, so a file that references
+        // the same (e.g. empty-expanding) nested file over and over can't fan out unbounded work
+        // while leaving the budget untouched.
+        let Some(remaining) = budget.checked_sub(1) else {
+            return Err(std::io::Error::other(format!(
+                "@{path}: expands to more than {MAX_ARGFILE_TOKENS} arguments"
+            )));
+        };
+        *budget = remaining;
+        if let Some(nested_path) = argfile_path(&token) {
+            expanded.extend(read_argfile(nested_path, depth + 1, budget)?);
+        } else {
+            expanded.push(token);
+        }
+    }
+    Ok(expanded)
+}
+
+/// Split `@file` contents into [`OsString`] tokens, honoring quotes and escapes the way rustc's
+/// own `@file` lexer does: outside quotes, whitespace separates tokens and a backslash escapes
+/// the next byte literally; inside `"..."` everything is literal except `\"` and `\\`; inside
+/// `'...'` everything, including backslashes, is literal.
+fn tokenize_argfile(contents: &[u8]) -> std::io::Result<Vec<OsString>> {
+    raw_tokenize(contents)
+        .into_iter()
+        .map(wtf8_bytes_to_os_string)
+        .collect()
+}
+
+/// Convert a token produced by [`raw_tokenize`] into an [`OsString`].
+///
+/// On Unix (and other platforms where [`OsStr`] is just an arbitrary byte string, such as WASI),
+/// this is infallible: any byte sequence, valid WTF-8 or not, is a legal argument, so rejecting
+/// non-WTF-8 bytes here would be spuriously stricter than a normal argv value ever is.
+#[cfg(any(unix, target_os = "wasi"))]
+fn wtf8_bytes_to_os_string(bytes: Vec<u8>) -> std::io::Result<OsString> {
+    #[cfg(unix)]
+    use std::os::unix::ffi::OsStringExt;
+    #[cfg(target_os = "wasi")]
+    use std::os::wasi::ffi::OsStringExt;
+
+    Ok(OsString::from_vec(bytes))
+}
+
+/// Convert a token produced by [`raw_tokenize`] into an [`OsString`].
+///
+/// On platforms (such as Windows) where [`OsStr`] is backed by WTF-8, not every byte sequence is
+/// a valid platform string, and `os_str_bytes`'s fallible raw-bytes conversions
+/// (`RawOsStr::cow_from_raw_bytes` and friends) are gated behind the `checked_conversions`
+/// feature, which in turn refuses to build unless the consumer sets the
+/// `OS_STR_BYTES_CHECKED_CONVERSIONS` environment variable, so they're not usable here. Instead,
+/// validate the bytes ourselves with the same WTF-8 decoder [`ShortFlags`] uses, then hand them
+/// to the `unsafe`, infallible constructor — the validation above is exactly the safety
+/// invariant that constructor asks callers to uphold.
+#[cfg(not(any(unix, target_os = "wasi")))]
+fn wtf8_bytes_to_os_string(bytes: Vec<u8>) -> std::io::Result<OsString> {
+    if !is_wtf8(&bytes) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "invalid OS string in @file",
+        ));
+    }
+    // SAFETY: `is_wtf8` just confirmed `bytes` is well-formed WTF-8.
+    let raw = unsafe { RawOsStr::cow_from_raw_bytes_unchecked(&bytes) };
+    Ok(raw.to_os_str().into_owned())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ArgfileQuote {
+    None,
+    Single,
+    Double,
+}
+
+fn raw_tokenize(contents: &[u8]) -> Vec<Vec<u8>> {
+    let mut tokens = Vec::new();
+    let mut current = Vec::new();
+    let mut in_token = false;
+    let mut quote = ArgfileQuote::None;
+    let mut bytes = contents.iter().copied().peekable();
+
+    while let Some(b) = bytes.next() {
+        match quote {
+            ArgfileQuote::None => match b {
+                b' ' | b'\t' | b'\n' | b'\r' => {
+                    if in_token {
+                        tokens.push(std::mem::take(&mut current));
+                        in_token = false;
+                    }
+                }
+                b'\\' => {
+                    if let Some(escaped) = bytes.next() {
+                        current.push(escaped);
+                    }
+                    in_token = true;
+                }
+                b'\'' => {
+                    quote = ArgfileQuote::Single;
+                    in_token = true;
+                }
+                b'"' => {
+                    quote = ArgfileQuote::Double;
+                    in_token = true;
+                }
+                _ => {
+                    current.push(b);
+                    in_token = true;
+                }
+            },
+            ArgfileQuote::Single => {
+                if b == b'\'' {
+                    quote = ArgfileQuote::None;
+                } else {
+                    current.push(b);
+                }
+            }
+            ArgfileQuote::Double => match b {
+                b'"' => quote = ArgfileQuote::None,
+                b'\\' => match bytes.peek() {
+                    Some(b'"') => {
+                        bytes.next();
+                        current.push(b'"');
+                    }
+                    Some(b'\\') => {
+                        bytes.next();
+                        current.push(b'\\');
+                    }
+                    _ => current.push(b'\\'),
+                },
+                _ => current.push(b),
+            },
+        }
+    }
+    if in_token || quote != ArgfileQuote::None {
+        tokens.push(current);
+    }
+
+    tokens
 }
 
 impl<I, T> From<I> for RawArgs
@@ -66,6 +320,8 @@ where
     fn from(val: I) -> Self {
         Self {
             items: val.map(|x| x.into()).collect(),
+            argfiles_enabled: false,
+            last_argfile_error: None,
         }
     }
 }
@@ -103,9 +359,21 @@ impl<'s> ParsedArg<'s> {
     }
 
     pub fn is_number(&self) -> bool {
-        self.to_value()
-            .map(|s| s.parse::<f64>().is_ok())
-            .unwrap_or_default()
+        self.number().is_some()
+    }
+
+    /// Classify this argument as a number, if it looks like one
+    ///
+    /// This drives the "is this a negative number or a flag?" decision for
+    /// `allow_negative_numbers`/`allow_hyphen_values`. It is a superset of what
+    /// [`f64::from_str`] accepts (including a bare leading/trailing `.` and `inf`/`infinity`/
+    /// `nan`), additionally recognizing the `0x`/`0o`/`0b` radix prefixes and `_` digit
+    /// separators, and it reports which form matched so a value parser doesn't need to re-scan
+    /// the raw string. In a decimal number, a `_` may not start the integer, fraction or exponent
+    /// digits (`_5`, `1._5`), but may follow any digit, including at the end (`5_`); after a
+    /// `0x`/`0o`/`0b` prefix it may appear anywhere, as long as one digit is present (`0x_FF`).
+    pub fn number(&self) -> Option<NumberKind> {
+        self.to_value().and_then(classify_number)
     }
 
     /// Treat as a long-flag
@@ -178,23 +446,25 @@ impl<'s> ParsedArg<'s> {
 #[derive(Clone, Debug)]
 pub(crate) struct ShortFlags<'s> {
     inner: &'s RawOsStr,
-    utf8_prefix: std::str::CharIndices<'s>,
-    invalid_suffix: Option<&'s RawOsStr>,
+    state: ShortFlagsState<'s>,
+}
+
+/// Fast path for fully-UTF-8 input reuses `CharIndices`; anything else falls back to decoding
+/// the raw bytes (including WTF-8's surrogate encoding) one unit at a time.
+#[derive(Clone, Debug)]
+enum ShortFlagsState<'s> {
+    Utf8(std::str::CharIndices<'s>),
+    Wtf8 { pos: usize },
 }
 
 impl<'s> ShortFlags<'s> {
     fn new(inner: &'s RawOsStr, utf8: Option<&'s str>) -> Self {
-        let (utf8_prefix, invalid_suffix) = if let Some(utf8) = utf8 {
-            (utf8, None)
+        let state = if let Some(utf8) = utf8 {
+            ShortFlagsState::Utf8(utf8.char_indices())
         } else {
-            split_nonutf8_once(inner)
+            ShortFlagsState::Wtf8 { pos: 0 }
         };
-        let utf8_prefix = utf8_prefix.char_indices();
-        Self {
-            inner,
-            utf8_prefix,
-            invalid_suffix,
-        }
+        Self { inner, state }
     }
 
     pub fn advance_by(&mut self, n: usize) -> Result<(), usize> {
@@ -205,39 +475,71 @@ impl<'s> ShortFlags<'s> {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.invalid_suffix.is_none() && self.utf8_prefix.as_str().is_empty()
+        match &self.state {
+            ShortFlagsState::Utf8(chars) => chars.as_str().is_empty(),
+            ShortFlagsState::Wtf8 { pos } => *pos >= self.inner.raw_len(),
+        }
     }
 
     pub fn is_number(&self) -> bool {
-        self.invalid_suffix.is_none() && self.utf8_prefix.as_str().parse::<f64>().is_ok()
+        self.number().is_some()
     }
 
-    pub fn next(&mut self) -> Option<Result<char, &'s RawOsStr>> {
-        if let Some((_, flag)) = self.utf8_prefix.next() {
-            return Some(Ok(flag));
+    /// Classify the remaining flags as a number, if they look like one
+    ///
+    /// See [`ParsedArg::number`] for the forms recognized.
+    pub fn number(&self) -> Option<NumberKind> {
+        match &self.state {
+            ShortFlagsState::Utf8(chars) => classify_number(chars.as_str()),
+            ShortFlagsState::Wtf8 { pos } => {
+                std::str::from_utf8(&self.inner.as_raw_bytes()[*pos..])
+                    .ok()
+                    .and_then(classify_number)
+            }
         }
+    }
 
-        if let Some(suffix) = self.invalid_suffix {
-            self.invalid_suffix = None;
-            return Some(Err(suffix));
+    pub fn next(&mut self) -> Option<Result<char, &'s RawOsStr>> {
+        match &mut self.state {
+            ShortFlagsState::Utf8(chars) => chars.next().map(|(_, flag)| Ok(flag)),
+            ShortFlagsState::Wtf8 { pos } => {
+                let bytes = self.inner.as_raw_bytes();
+                if *pos >= bytes.len() {
+                    return None;
+                }
+                let start = *pos;
+                let unit = decode_wtf8_unit(&bytes[start..]);
+                *pos += unit.len();
+                match unit {
+                    Wtf8Unit::Scalar(c, _) => Some(Ok(c)),
+                    Wtf8Unit::Surrogate(len) | Wtf8Unit::Invalid(len) => {
+                        Some(Err(&self.inner[start..start + len]))
+                    }
+                }
+            }
         }
-
-        None
     }
 
     pub fn value_os(&mut self) -> Option<&'s RawOsStr> {
-        if let Some((index, _)) = self.utf8_prefix.next() {
-            self.utf8_prefix = "".char_indices();
-            self.invalid_suffix = None;
-            return Some(&self.inner[index..]);
-        }
-
-        if let Some(suffix) = self.invalid_suffix {
-            self.invalid_suffix = None;
-            return Some(suffix);
+        match &mut self.state {
+            ShortFlagsState::Utf8(chars) => {
+                let rest = chars.as_str();
+                if rest.is_empty() {
+                    return None;
+                }
+                let index = self.inner.raw_len() - rest.len();
+                *chars = "".char_indices();
+                Some(&self.inner[index..])
+            }
+            ShortFlagsState::Wtf8 { pos } => {
+                let start = *pos;
+                if start >= self.inner.raw_len() {
+                    return None;
+                }
+                *pos = self.inner.raw_len();
+                Some(&self.inner[start..])
+            }
         }
-
-        None
     }
 }
 
@@ -249,13 +551,519 @@ impl<'s> Iterator for ShortFlags<'s> {
     }
 }
 
-fn split_nonutf8_once(b: &RawOsStr) -> (&str, Option<&RawOsStr>) {
-    match std::str::from_utf8(b.as_raw_bytes()) {
-        Ok(s) => (s, None),
-        Err(err) => {
-            let (valid, after_valid) = b.split_at(err.valid_up_to());
-            let valid = std::str::from_utf8(valid.as_raw_bytes()).unwrap();
-            (valid, Some(after_valid))
+enum Wtf8Unit {
+    /// A decoded Unicode scalar value and the number of bytes it occupied.
+    Scalar(char, usize),
+    /// A lone (unpaired) surrogate of this many bytes: valid WTF-8, but not a `char`.
+    Surrogate(usize),
+    /// A genuinely malformed byte sequence of this many bytes.
+    Invalid(usize),
+}
+
+impl Wtf8Unit {
+    fn len(&self) -> usize {
+        match self {
+            Self::Scalar(_, len) | Self::Surrogate(len) | Self::Invalid(len) => *len,
+        }
+    }
+}
+
+/// Decode one WTF-8 unit from the start of `bytes`.
+///
+/// This walks the bytes like a UTF-8 decoder but additionally accepts the 3-byte surrogate
+/// encodings (lead byte `0xED`, second byte `0xA0..=0xBF`) that WTF-8 uses to represent
+/// unpaired surrogates (U+D800..=U+DFFF), which `std::str::from_utf8` rejects outright.
+fn decode_wtf8_unit(bytes: &[u8]) -> Wtf8Unit {
+    let b0 = bytes[0];
+    if b0 < 0x80 {
+        return Wtf8Unit::Scalar(b0 as char, 1);
+    }
+
+    let (len, b1_range) = match b0 {
+        0xC2..=0xDF => (2, 0x80..=0xBF),
+        0xE0 => (3, 0xA0..=0xBF),
+        0xE1..=0xEC => (3, 0x80..=0xBF),
+        0xED => (3, 0x80..=0xBF),
+        0xEE..=0xEF => (3, 0x80..=0xBF),
+        0xF0 => (4, 0x90..=0xBF),
+        0xF1..=0xF3 => (4, 0x80..=0xBF),
+        0xF4 => (4, 0x80..=0x8F),
+        _ => return Wtf8Unit::Invalid(1),
+    };
+    if bytes.len() < len || !b1_range.contains(&bytes[1]) {
+        return Wtf8Unit::Invalid(1);
+    }
+    if bytes[2..len].iter().any(|b| b & 0xC0 != 0x80) {
+        return Wtf8Unit::Invalid(1);
+    }
+
+    if b0 == 0xED && (0xA0..=0xBF).contains(&bytes[1]) {
+        // A surrogate code point: valid WTF-8, invalid UTF-8.
+        return Wtf8Unit::Surrogate(3);
+    }
+
+    let cp = match len {
+        2 => (u32::from(b0) & 0x1F) << 6 | (u32::from(bytes[1]) & 0x3F),
+        3 => {
+            (u32::from(b0) & 0x0F) << 12
+                | (u32::from(bytes[1]) & 0x3F) << 6
+                | (u32::from(bytes[2]) & 0x3F)
+        }
+        4 => {
+            (u32::from(b0) & 0x07) << 18
+                | (u32::from(bytes[1]) & 0x3F) << 12
+                | (u32::from(bytes[2]) & 0x3F) << 6
+                | (u32::from(bytes[3]) & 0x3F)
+        }
+        _ => unreachable!(),
+    };
+    match char::from_u32(cp) {
+        Some(c) => Wtf8Unit::Scalar(c, len),
+        None => Wtf8Unit::Invalid(len),
+    }
+}
+
+/// Is `bytes` well-formed WTF-8 in its entirety, including lone surrogates?
+///
+/// Used by the WTF-8-backed [`wtf8_bytes_to_os_string`] to confirm `@file` token bytes are valid
+/// for the `os_str_bytes` "unspecified encoding" before handing them to an infallible/`unsafe`
+/// constructor; reuses [`decode_wtf8_unit`], the same per-unit decoder [`ShortFlags`] drives
+/// incrementally.
+#[cfg(not(any(unix, target_os = "wasi")))]
+fn is_wtf8(mut bytes: &[u8]) -> bool {
+    let mut after_lead_surrogate = false;
+    while !bytes.is_empty() {
+        match decode_wtf8_unit(bytes) {
+            Wtf8Unit::Scalar(_, len) => {
+                after_lead_surrogate = false;
+                bytes = &bytes[len..];
+            }
+            Wtf8Unit::Surrogate(len) => {
+                // A lead surrogate (`0xED 0xA0..=0xAF`) directly followed by a trail surrogate
+                // (`0xED 0xB0..=0xBF`) is a paired surrogate, which WTF-8 encodes as one 4-byte
+                // scalar instead, so the pair is not a lone surrogate and not well-formed.
+                let is_trail = bytes[1] >= 0xB0;
+                if after_lead_surrogate && is_trail {
+                    return false;
+                }
+                after_lead_surrogate = !is_trail;
+                bytes = &bytes[len..];
+            }
+            Wtf8Unit::Invalid(_) => return false,
+        }
+    }
+    true
+}
+
+/// The form a numeric-looking argument ([`ParsedArg::number`], [`ShortFlags::number`]) took
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum NumberKind {
+    /// A base-10 integer, e.g. `-123` or `1_000_000`
+    Decimal,
+    /// A base-10 number with a fractional part and/or exponent, e.g. `1.5` or `1e9`
+    Float,
+    /// A `0x`/`0X`-prefixed integer, e.g. `0xFF`
+    Hex,
+    /// A `0o`/`0O`-prefixed integer, e.g. `0o755`
+    Octal,
+    /// A `0b`/`0B`-prefixed integer, e.g. `0b1010`
+    Binary,
+}
+
+/// Classify `s` as a number the way Rust's own integer/float literals are lexed: an optional
+/// sign, `0x`/`0o`/`0b` radix prefixes with their matching digit classes, `_` digit separators
+/// (see [`ParsedArg::number`] for where they may appear), and decimal/float forms with exponents.
+fn classify_number(s: &str) -> Option<NumberKind> {
+    let s = s.strip_prefix(['+', '-']).unwrap_or(s);
+
+    if let Some(digits) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return has_digits(digits, 16).then_some(NumberKind::Hex);
+    }
+    if let Some(digits) = s.strip_prefix("0o").or_else(|| s.strip_prefix("0O")) {
+        return has_digits(digits, 8).then_some(NumberKind::Octal);
+    }
+    if let Some(digits) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
+        return has_digits(digits, 2).then_some(NumberKind::Binary);
+    }
+
+    classify_decimal(s)
+}
+
+/// `true` if `s` is a non-empty run of base-`radix` digits, optionally interspersed with `_`
+fn has_digits(s: &str, radix: u32) -> bool {
+    !s.is_empty()
+        && s.chars().any(|c| c.is_digit(radix))
+        && s.chars().all(|c| c.is_digit(radix) || c == '_')
+}
+
+/// `f64::from_str` also accepts these case-insensitively (with an optional sign, already
+/// stripped by [`classify_number`] before this runs); keep recognizing them so switching from
+/// `f64::from_str` to [`classify_number`] doesn't quietly drop previously-valid arguments.
+fn classify_decimal(s: &str) -> Option<NumberKind> {
+    if s.eq_ignore_ascii_case("inf")
+        || s.eq_ignore_ascii_case("infinity")
+        || s.eq_ignore_ascii_case("nan")
+    {
+        return Some(NumberKind::Float);
+    }
+
+    let mut chars = s.chars().peekable();
+    let mut has_int_digits = false;
+    let mut has_frac_digits = false;
+    let mut is_float = false;
+
+    if matches!(chars.peek(), Some(c) if c.is_ascii_digit()) {
+        has_int_digits = true;
+        chars.next();
+        while matches!(chars.peek(), Some(c) if c.is_ascii_digit() || *c == '_') {
+            chars.next();
+        }
+    }
+
+    // A leading (`.5`) or trailing (`5.`) digit run is optional, matching what
+    // `f64::from_str` accepts, but a bare `.` with digits on neither side is not a number.
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        is_float = true;
+        if matches!(chars.peek(), Some(c) if c.is_ascii_digit()) {
+            has_frac_digits = true;
+            chars.next();
+            while matches!(chars.peek(), Some(c) if c.is_ascii_digit() || *c == '_') {
+                chars.next();
+            }
+        }
+    }
+    if !has_int_digits && !has_frac_digits {
+        return None;
+    }
+
+    if matches!(chars.peek(), Some('e' | 'E')) {
+        let mut lookahead = chars.clone();
+        lookahead.next();
+        if matches!(lookahead.peek(), Some('+' | '-')) {
+            lookahead.next();
+        }
+        if matches!(lookahead.peek(), Some(c) if c.is_ascii_digit()) {
+            is_float = true;
+            chars.next();
+            if matches!(chars.peek(), Some('+' | '-')) {
+                chars.next();
+            }
+            while matches!(chars.peek(), Some(c) if c.is_ascii_digit() || *c == '_') {
+                chars.next();
+            }
+        }
+    }
+
+    if chars.next().is_some() {
+        return None;
+    }
+
+    Some(if is_float {
+        NumberKind::Float
+    } else {
+        NumberKind::Decimal
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_number_decimal_and_float_forms() {
+        assert_eq!(classify_number("123"), Some(NumberKind::Decimal));
+        assert_eq!(classify_number("-123"), Some(NumberKind::Decimal));
+        assert_eq!(classify_number("+123"), Some(NumberKind::Decimal));
+        assert_eq!(classify_number("1_000_000"), Some(NumberKind::Decimal));
+        assert_eq!(classify_number("1.5"), Some(NumberKind::Float));
+        assert_eq!(classify_number("-5."), Some(NumberKind::Float));
+        assert_eq!(classify_number("-.5"), Some(NumberKind::Float));
+        assert_eq!(classify_number("1e9"), Some(NumberKind::Float));
+        assert_eq!(classify_number("1E-9"), Some(NumberKind::Float));
+    }
+
+    #[test]
+    fn classify_number_inf_and_nan() {
+        assert_eq!(classify_number("inf"), Some(NumberKind::Float));
+        assert_eq!(classify_number("-inf"), Some(NumberKind::Float));
+        assert_eq!(classify_number("infinity"), Some(NumberKind::Float));
+        assert_eq!(classify_number("NaN"), Some(NumberKind::Float));
+        assert_eq!(classify_number("-NAN"), Some(NumberKind::Float));
+    }
+
+    #[test]
+    fn classify_number_radix_prefixes() {
+        assert_eq!(classify_number("0xFF"), Some(NumberKind::Hex));
+        assert_eq!(classify_number("-0x1F"), Some(NumberKind::Hex));
+        assert_eq!(classify_number("0o755"), Some(NumberKind::Octal));
+        assert_eq!(classify_number("0b1010"), Some(NumberKind::Binary));
+        assert_eq!(classify_number("0x_FF"), Some(NumberKind::Hex));
+        assert_eq!(classify_number("0x"), None);
+        assert_eq!(classify_number("0xGG"), None);
+    }
+
+    #[test]
+    fn classify_number_rejects_leading_underscore_and_garbage() {
+        assert_eq!(classify_number("_5"), None);
+        assert_eq!(classify_number("-_5"), None);
+        assert_eq!(classify_number("."), None);
+        assert_eq!(classify_number("1..2"), None);
+        assert_eq!(classify_number("abc"), None);
+        assert_eq!(classify_number(""), None);
+    }
+
+    #[test]
+    fn classify_number_accepts_trailing_underscore() {
+        // `5_` is a valid Rust `DEC_LITERAL` (underscores are allowed anywhere after the first
+        // digit), so this is intentionally accepted, unlike a leading underscore.
+        assert_eq!(classify_number("5_"), Some(NumberKind::Decimal));
+    }
+
+    #[test]
+    fn short_flags_iterates_ascii_flags() {
+        let arg = ParsedArg::new(OsStr::new("-abc"));
+        let mut flags = arg.to_short().expect("looks like a short-flag bundle");
+        assert_eq!(flags.next(), Some(Ok('a')));
+        assert_eq!(flags.next(), Some(Ok('b')));
+        assert_eq!(flags.next(), Some(Ok('c')));
+        assert_eq!(flags.next(), None);
+    }
+
+    #[test]
+    fn decode_wtf8_unit_recognizes_scalars_and_surrogates() {
+        assert!(matches!(decode_wtf8_unit(b"a"), Wtf8Unit::Scalar('a', 1)));
+        assert!(matches!(
+            decode_wtf8_unit("é".as_bytes()),
+            Wtf8Unit::Scalar('é', 2)
+        ));
+        // `0xED 0xA0 0x80` is the WTF-8 encoding of the lone (unpaired) surrogate U+D800, which
+        // `std::str::from_utf8` rejects outright.
+        assert!(matches!(
+            decode_wtf8_unit(&[0xED, 0xA0, 0x80]),
+            Wtf8Unit::Surrogate(3)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn short_flags_skip_lone_surrogate_and_keep_trailing_flag() {
+        use std::os::unix::ffi::OsStrExt;
+
+        // `-a<surrogate>b`: a bundled short-flag group where the byte in the middle is an
+        // unpaired surrogate. Decoding must not swallow the trailing `b`.
+        let bytes = [b'-', b'a', 0xED, 0xA0, 0x80, b'b'];
+        let arg = ParsedArg::new(OsStr::from_bytes(&bytes));
+        let mut flags = arg.to_short().expect("looks like a short-flag bundle");
+
+        assert_eq!(flags.next(), Some(Ok('a')));
+        match flags.next() {
+            Some(Err(invalid)) => assert_eq!(invalid.as_raw_bytes(), &[0xED, 0xA0, 0x80]),
+            other => panic!("expected the lone surrogate as an `Err` unit, got {other:?}"),
         }
+        assert_eq!(flags.next(), Some(Ok('b')));
+        assert_eq!(flags.next(), None);
+    }
+
+    #[test]
+    fn raw_tokenize_splits_on_whitespace() {
+        assert_eq!(
+            raw_tokenize(b"  --foo   bar\tbaz\n"),
+            vec![b"--foo".to_vec(), b"bar".to_vec(), b"baz".to_vec()]
+        );
+    }
+
+    #[test]
+    fn raw_tokenize_honors_double_quotes_and_their_escapes() {
+        // Inside `"..."`, everything is literal except `\"` and `\\`; an unrecognized escape
+        // like `\n` keeps its backslash.
+        assert_eq!(
+            raw_tokenize(br#""a b\"c\\d\ne""#),
+            vec![br#"a b"c\d\ne"#.to_vec()]
+        );
+    }
+
+    #[test]
+    fn raw_tokenize_honors_single_quotes_as_fully_literal() {
+        assert_eq!(raw_tokenize(br"'a\b c'"), vec![br"a\b c".to_vec()]);
+    }
+
+    #[test]
+    fn raw_tokenize_backslash_escapes_outside_quotes() {
+        assert_eq!(raw_tokenize(br"a\ b"), vec![b"a b".to_vec()]);
+    }
+
+    #[test]
+    fn raw_tokenize_tolerates_unterminated_quote_and_trailing_backslash() {
+        // An unterminated quote or a trailing backslash with nothing left to escape shouldn't
+        // panic or drop the in-progress token; it's simply the last token as typed.
+        assert_eq!(
+            raw_tokenize(br#""unterminated"#),
+            vec![b"unterminated".to_vec()]
+        );
+        assert_eq!(raw_tokenize(br"trailing\"), vec![b"trailing".to_vec()]);
+    }
+
+    #[test]
+    fn tokenize_argfile_rejects_invalid_wtf8_only_where_os_str_is_wtf8() {
+        // `0xED 0xA0 0x80` is a lone surrogate: valid WTF-8, so this should succeed everywhere...
+        assert!(tokenize_argfile(&[0xED, 0xA0, 0x80]).is_ok());
+
+        // ...but a bare continuation byte is not valid WTF-8 at all. On platforms (such as
+        // Windows) where `OsStr` is WTF-8-backed, that's rejected; on Unix (and wasi), `OsStr` is
+        // just an arbitrary byte string, so it's a perfectly legal argument.
+        #[cfg(any(unix, target_os = "wasi"))]
+        assert!(tokenize_argfile(&[0x80]).is_ok());
+        #[cfg(not(any(unix, target_os = "wasi")))]
+        assert!(tokenize_argfile(&[0x80]).is_err());
+    }
+
+    /// A file under `std::env::temp_dir()` that is removed when dropped, for tests that need
+    /// `read_argfile`/`RawArgs::expand_argfiles` to read real paths off disk.
+    struct TempArgfile {
+        path: std::path::PathBuf,
+    }
+
+    impl TempArgfile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            use std::sync::atomic::AtomicUsize;
+            use std::sync::atomic::Ordering;
+
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let unique = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir().join(format!(
+                "clap-lexer-test-{}-{unique}-{name}",
+                std::process::id()
+            ));
+            std::fs::write(&path, contents).expect("write temp argfile");
+            Self { path }
+        }
+
+        fn at_sign_arg(&self) -> String {
+            format!("@{}", self.path.display())
+        }
+    }
+
+    impl Drop for TempArgfile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn read_argfile_expands_tokens_from_disk() {
+        let file = TempArgfile::new("basic", b"--foo bar 'baz qux'");
+        let mut budget = MAX_ARGFILE_TOKENS;
+        let tokens = read_argfile(file.path.to_str().unwrap(), 0, &mut budget).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                OsString::from("--foo"),
+                OsString::from("bar"),
+                OsString::from("baz qux"),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_argfile_expands_nested_argfiles_recursively() {
+        let inner = TempArgfile::new("inner", b"--inner-flag");
+        let outer = TempArgfile::new("outer", inner.at_sign_arg().as_bytes());
+        let mut budget = MAX_ARGFILE_TOKENS;
+        let tokens = read_argfile(outer.path.to_str().unwrap(), 0, &mut budget).unwrap();
+        assert_eq!(tokens, vec![OsString::from("--inner-flag")]);
+    }
+
+    #[test]
+    fn read_argfile_rejects_self_referential_nesting() {
+        let file = TempArgfile::new("cycle", b"placeholder");
+        // Overwrite with a reference to itself once we know its own path.
+        std::fs::write(&file.path, file.at_sign_arg()).unwrap();
+        let mut budget = MAX_ARGFILE_TOKENS;
+        let err = read_argfile(file.path.to_str().unwrap(), 0, &mut budget).unwrap_err();
+        assert!(err.to_string().contains("too many levels"));
+    }
+
+    #[test]
+    fn read_argfile_enforces_token_budget() {
+        let file = TempArgfile::new("big", b"a b c");
+        let mut budget = 2;
+        let err = read_argfile(file.path.to_str().unwrap(), 0, &mut budget).unwrap_err();
+        assert!(err.to_string().contains("more than"));
+    }
+
+    #[test]
+    fn read_argfile_charges_budget_per_nested_reference_even_when_empty() {
+        // A reference to an empty (zero-token) nested file still has to consume budget, or a
+        // file that references it over and over bypasses `MAX_ARGFILE_TOKENS` entirely.
+        let inner = TempArgfile::new("empty-inner", b"");
+        let refs = std::iter::repeat_n(inner.at_sign_arg(), 4)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let outer = TempArgfile::new("many-empty-refs", refs.as_bytes());
+        let mut budget = 3;
+        let err = read_argfile(outer.path.to_str().unwrap(), 0, &mut budget).unwrap_err();
+        assert!(err.to_string().contains("more than"));
+    }
+
+    #[test]
+    fn raw_args_lazily_expands_argfiles_via_next() {
+        let file = TempArgfile::new("lazy", b"--from-file");
+        let mut args = RawArgs::from([String::from("prog"), file.at_sign_arg()].into_iter());
+        args.set_argfiles_enabled(true);
+        let mut cursor = args.cursor();
+
+        assert_eq!(args.next(&mut cursor).unwrap().to_value(), Some("prog"));
+        assert_eq!(
+            args.next(&mut cursor).unwrap().to_value(),
+            Some("--from-file")
+        );
+        assert_eq!(args.next(&mut cursor), None);
+    }
+
+    #[test]
+    fn raw_args_surfaces_lazy_expansion_errors() {
+        let missing = std::env::temp_dir().join("clap-lexer-test-definitely-missing-argfile");
+        let mut args = RawArgs::from([format!("@{}", missing.display())].into_iter());
+        args.set_argfiles_enabled(true);
+        let mut cursor = args.cursor();
+
+        // The unreadable path is handed back as a literal argument...
+        assert!(args.next(&mut cursor).is_some());
+        // ...but the real reason is available for a caller that wants a proper diagnostic.
+        assert!(args.take_argfile_error().is_some());
+        // Taking it clears it until the next expansion attempt.
+        assert!(args.take_argfile_error().is_none());
+    }
+
+    #[test]
+    fn raw_args_next_expands_through_a_zero_token_argfile() {
+        // An `@file` that expands to nothing (empty, whitespace-only, ...) shifts the next item
+        // into its slot without advancing past it; that item must still get a chance to expand
+        // in the same `next` call, rather than being handed back as an unexpanded literal.
+        let empty = TempArgfile::new("empty", b"");
+        let real = TempArgfile::new("real", b"--real-flag");
+        let mut args = RawArgs::from([empty.at_sign_arg(), real.at_sign_arg()].into_iter());
+        args.set_argfiles_enabled(true);
+        let mut cursor = args.cursor();
+
+        assert_eq!(
+            args.next(&mut cursor).unwrap().to_value(),
+            Some("--real-flag")
+        );
+        assert_eq!(args.next(&mut cursor), None);
+    }
+
+    #[test]
+    fn expand_argfiles_expands_eagerly_in_place() {
+        let file = TempArgfile::new("eager", b"--foo --bar");
+        let mut args = RawArgs::from([file.at_sign_arg()].into_iter());
+        let cursor = args.cursor();
+        args.expand_argfiles(&cursor).unwrap();
+
+        let mut cursor = args.cursor();
+        assert_eq!(args.next(&mut cursor).unwrap().to_value(), Some("--foo"));
+        assert_eq!(args.next(&mut cursor).unwrap().to_value(), Some("--bar"));
+        assert_eq!(args.next(&mut cursor), None);
     }
 }